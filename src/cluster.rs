@@ -16,6 +16,17 @@ pub struct Cluster<T> {
     pub indices: Indices,
     pub name: String,
     pub children: Children<T>,
+    /// The index of the point this cluster was split around, i.e. the pole it
+    /// was assigned to in its parent's partition. `None` for the root.
+    pub pole: Option<Index>,
+    /// The maximum distance from `pole` to any point in `indices`.
+    pub radius: Radius,
+    /// Aggregate anomaly score in `[0, 1]` for this cluster, combining how
+    /// small it is relative to its parent and how imbalanced the split that
+    /// produced it was, averaged over every split from the root down to here
+    /// (not just the one that produced this cluster), plus how shallow it
+    /// detached at. `0` for the root.
+    pub score: f64,
 }
 
 impl<T> PartialEq for Cluster<T> {
@@ -45,6 +56,9 @@ impl<T> Cluster<T> {
             indices,
             name: String::from(""),
             children: None,
+            pole: None,
+            radius: 0.,
+            score: 0.,
         }
     }
 
@@ -63,39 +77,157 @@ impl<T> Cluster<T> {
         }
     }
 
+    pub fn leaves(&self, depth: usize) -> Vec<&Cluster<T>> {
+        if self.depth() == depth {
+            vec![self]
+        } else {
+            match self.children.as_ref() {
+                Some(c) => c.iter().flat_map(|c| c.leaves(depth)).collect(),
+                None => vec![self],
+            }
+        }
+    }
+}
+
+impl<T: Copy + Into<f64> + Sync> Cluster<T> {
+    /// Distance between two points of the dataset, as seen from this
+    /// cluster's `indices`. Delegates to the dataset's cache so repeated
+    /// requests (e.g. from `partition` and `pole_distance`) aren't
+    /// recomputed.
+    fn distance(&self, i: Index, j: Index) -> Radius {
+        self.dataset.distance(i, j)
+    }
+
+    /// Distance between this cluster's pole and `other`'s, or `None` if
+    /// either is the root (and so has no pole). Used by
+    /// `Manifold::component_graph` to decide whether two clusters overlap.
+    pub(crate) fn pole_distance(&self, other: &Cluster<T>) -> Option<Radius> {
+        match (self.pole, other.pole) {
+            (Some(a), Some(b)) => Some(self.distance(a, b)),
+            _ => None,
+        }
+    }
+
+    /// The index (within `self.indices`) farthest from `from`, breaking ties
+    /// in favor of the first index encountered.
+    fn farthest_from(&self, from: Index) -> Index {
+        self.indices
+            .iter()
+            .copied()
+            .fold((from, -1.), |(best, best_dist), i| {
+                let dist = self.distance(from, i);
+                if dist > best_dist {
+                    (i, dist)
+                } else {
+                    (best, best_dist)
+                }
+            })
+            .0
+    }
+
+    /// Splits `self.indices` into `K` subsets by picking two poles `l` and
+    /// `r` (`l` farthest from an arbitrary seed, `r` farthest from `l`) and
+    /// assigning every index to whichever pole it is closer to, then
+    /// recurses into each child so the whole subtree is built, not just one
+    /// level of it. Ties are broken in favor of `l`. Each child records its
+    /// pole and radius (the farthest distance from that pole among the
+    /// points assigned to it) so that later search can prune by the
+    /// triangle inequality.
     pub fn partition(self, criteria: &Vec<impl Criterion<T>>) -> Cluster<T> {
         for criterion in criteria.iter() {
             if criterion.check(&self) == false {
                 return self;
             }
         }
-        let mut children = Vec::new();
-        for i in 0..K {
-            let c = Cluster::<T> {
-                dataset: Rc::clone(&self.dataset),
-                indices: vec![0],
-                name: format!("{}{}", self.name, i),
-                children: None,
-            };
-            children.push(Rc::new(c));
+
+        let seed = self.indices[0];
+        let l = self.farthest_from(seed);
+        let r = self.farthest_from(l);
+
+        let mut left_indices = Indices::new();
+        let mut right_indices = Indices::new();
+        for &i in self.indices.iter() {
+            if self.distance(i, l) <= self.distance(i, r) {
+                left_indices.insert(i);
+            } else {
+                right_indices.insert(i);
+            }
+        }
+
+        // All points tied with each other (e.g. duplicate coordinates) land
+        // on the same side, so the split makes no progress. Stop here
+        // rather than recursing forever on an unchanged cluster.
+        if left_indices.is_empty() || right_indices.is_empty() {
+            return self;
         }
 
+        let left_radius = left_indices
+            .iter()
+            .map(|&i| self.distance(i, l))
+            .fold(0., f64::max);
+        let right_radius = right_indices
+            .iter()
+            .map(|&i| self.distance(i, r))
+            .fold(0., f64::max);
+
+        let parent_cardinality = self.cardinality() as f64;
+        let child_depth = self.depth() + 1;
+        let imbalance = (left_indices.len() as f64 - right_indices.len() as f64).abs()
+            / parent_cardinality;
+        // Number of splits already folded into `self.score` (0 at the root,
+        // which hasn't been through any split yet).
+        let ancestors = self.depth() as f64;
+        // `self.score` blends that running average half-and-half with its
+        // own depth signal; undo the blend to recover the average alone.
+        let ancestor_average = if self.depth() == 0 {
+            0.
+        } else {
+            2. * self.score - 1. / (self.depth() + 1) as f64
+        };
+        let score_for = |child_cardinality: usize| -> f64 {
+            let cardinality_score = 1. - child_cardinality as f64 / parent_cardinality;
+            let local = (cardinality_score + imbalance) / 2.;
+            // Running average of this split's signal with every ancestor's,
+            // so a lopsided split higher up the tree still shows up in a
+            // leaf's score even when the split that produced the leaf itself
+            // happened to be balanced.
+            let accumulated = (ancestor_average * ancestors + local) / (ancestors + 1.);
+            let depth_score = 1. / (child_depth + 1) as f64;
+            (accumulated + depth_score) / 2.
+        };
+
+        let left_child = Cluster::<T> {
+            dataset: Rc::clone(&self.dataset),
+            score: score_for(left_indices.len()),
+            indices: left_indices,
+            name: format!("{}{}", self.name, 0),
+            children: None,
+            pole: Some(l),
+            radius: left_radius,
+        }
+        .partition(criteria);
+        let right_child = Cluster::<T> {
+            dataset: Rc::clone(&self.dataset),
+            score: score_for(right_indices.len()),
+            indices: right_indices,
+            name: format!("{}{}", self.name, 1),
+            children: None,
+            pole: Some(r),
+            radius: right_radius,
+        }
+        .partition(criteria);
+
+        let children = vec![Rc::new(left_child), Rc::new(right_child)];
+        debug_assert_eq!(children.len(), K as usize);
+
         Cluster::<T> {
             dataset: self.dataset,
             indices: self.indices,
             name: self.name,
             children: Some(children),
-        }
-    }
-
-    pub fn leaves(&self, depth: usize) -> Vec<&Cluster<T>> {
-        if self.depth() == depth {
-            vec![self]
-        } else {
-            match self.children.as_ref() {
-                Some(c) => c.iter().flat_map(|c| c.leaves(depth)).collect(),
-                None => vec![self],
-            }
+            pole: self.pole,
+            radius: self.radius,
+            score: self.score,
         }
     }
 }
@@ -103,13 +235,15 @@ impl<T> Cluster<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ndarray::arr2;
     use std::collections::hash_map::DefaultHasher;
 
     fn dataset() -> Rc<Dataset<u64>> {
-        Rc::new(Dataset {
-            data: Box::new(Data::<u64>::zeros((2, 2))),
-            metric: String::from("euclidean"),
-        })
+        Rc::new(Dataset::new(Data::<u64>::zeros((2, 2)), String::from("euclidean")))
+    }
+
+    fn indices(v: Vec<Index>) -> Indices {
+        v.into_iter().collect()
     }
 
     fn hash<T: Hash>(t: &T) -> u64 {
@@ -120,37 +254,75 @@ mod tests {
 
     #[test]
     fn hash_eq() {
-        let a = Cluster::new(dataset(), vec![0, 1]);
-        let b = Cluster::new(dataset(), vec![0, 1]);
+        let a = Cluster::new(dataset(), indices(vec![0, 1]));
+        let b = Cluster::new(dataset(), indices(vec![0, 1]));
         assert_eq!(a, b);
         assert_eq!(hash(&a), hash(&b));
     }
 
     #[test]
     fn cardinality() {
-        let c = Cluster::new(dataset(), vec![0, 1]);
+        let c = Cluster::new(dataset(), indices(vec![0, 1]));
         assert_eq!(c.cardinality(), 2);
-        let c = Cluster::new(dataset(), vec![0]);
+        let c = Cluster::new(dataset(), indices(vec![0]));
         assert_eq!(c.cardinality(), 1);
     }
 
     #[test]
     fn display() {
-        let c = Cluster::new(dataset(), vec![0, 1]);
+        let c = Cluster::new(dataset(), indices(vec![0, 1]));
         let s = format!("{}", c);
         assert_eq!(s, String::from(""));
     }
 
     #[test]
     fn depth() {
-        let c = Cluster::new(dataset(), vec![0, 1]);
+        let c = Cluster::new(dataset(), indices(vec![0, 1]));
         assert_eq!(c.depth(), 0);
         let c = Cluster::<u64> {
             dataset: dataset(),
-            indices: vec![0, 1],
+            indices: indices(vec![0, 1]),
             name: String::from("010"),
             children: None,
+            pole: None,
+            radius: 0.,
+            score: 0.,
         };
         assert_eq!(c.depth(), 3);
     }
+
+    #[test]
+    fn partition_splits_by_pole() {
+        let dataset = Rc::new(Dataset::new(
+            arr2(&[[0., 0.], [1., 0.], [10., 0.], [11., 0.]]),
+            String::from("euclidean"),
+        ));
+        let c = Cluster::new(dataset, indices(vec![0, 1, 2, 3]));
+        let c = c.partition(&Vec::<super::super::criteria::MinPoints>::new());
+        let children = c.children.unwrap();
+        assert_eq!(children.len(), 2);
+
+        let mut sizes: Vec<usize> = children.iter().map(|c| c.cardinality()).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![2, 2]);
+        assert!(children.iter().all(|c| c.pole.is_some()));
+        assert!(children.iter().all(|c| (0. ..=1.).contains(&c.score)));
+    }
+
+    #[test]
+    fn partition_recurses_until_singletons() {
+        let dataset = Rc::new(Dataset::new(
+            arr2(&[[0., 0.], [1., 0.], [10., 0.], [11., 0.]]),
+            String::from("euclidean"),
+        ));
+        let c = Cluster::new(dataset, indices(vec![0, 1, 2, 3]));
+        let c = c.partition(&Vec::<super::super::criteria::MinPoints>::new());
+
+        // With no stopping criteria, each cardinality-2 child should itself
+        // have split into two singleton leaves, not stopped at depth 1.
+        for child in c.children.unwrap().iter() {
+            let grandchildren = child.children.as_ref().expect("child should have recursed");
+            assert!(grandchildren.iter().all(|g| g.cardinality() == 1));
+        }
+    }
 }
@@ -1,33 +1,126 @@
 use super::types::*;
-use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct Dataset {
-    pub data: Box<Data>,
+/// Pluggable backing store for the condensed pairwise-distance cache used by
+/// [`Dataset::distance`]/[`Dataset::distance_matrix`]. `HashMapStore` (the
+/// default) never evicts; swap in `EvictingStore` for datasets too large to
+/// cache in full. `Send` so the cache can live behind a `Mutex` and be
+/// shared across the threads `distance_matrix` computes missing entries on.
+pub trait DistanceStore: Send {
+    fn get(&self, key: Index) -> Option<Radius>;
+    fn put(&mut self, key: Index, value: Radius);
+}
+
+/// Unbounded cache: every computed distance is kept for the life of the
+/// `Dataset`.
+#[derive(Debug, Default)]
+pub struct HashMapStore(HashMap<Index, Radius>);
+
+impl DistanceStore for HashMapStore {
+    fn get(&self, key: Index) -> Option<Radius> {
+        self.0.get(&key).copied()
+    }
+
+    fn put(&mut self, key: Index, value: Radius) {
+        self.0.insert(key, value);
+    }
+}
+
+/// Capacity-bounded cache: once `capacity` entries are cached, the oldest
+/// one is evicted (FIFO) to make room for the new one, so memory use never
+/// grows past `capacity` regardless of how many distances are requested.
+#[derive(Debug)]
+pub struct EvictingStore {
+    capacity: usize,
+    map: HashMap<Index, Radius>,
+    order: VecDeque<Index>,
+}
+
+impl EvictingStore {
+    pub fn new(capacity: usize) -> EvictingStore {
+        EvictingStore {
+            capacity,
+            map: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl DistanceStore for EvictingStore {
+    fn get(&self, key: Index) -> Option<Radius> {
+        self.map.get(&key).copied()
+    }
+
+    fn put(&mut self, key: Index, value: Radius) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.map.contains_key(&key) {
+            if self.map.len() >= self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.map.remove(&oldest);
+                }
+            }
+            self.order.push_back(key);
+        }
+        self.map.insert(key, value);
+    }
+}
+
+pub struct Dataset<T> {
+    pub data: Box<Data<T>>,
     pub metric: String,
-    pub history: HashMap<Index, Index>,
+    // `Mutex`, not `RefCell`: `distance_matrix` shares `&self` across rayon
+    // worker threads, and `RefCell` is never `Sync`.
+    cache: Mutex<Box<dyn DistanceStore>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Dataset<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Dataset")
+            .field("data", &self.data)
+            .field("metric", &self.metric)
+            .finish()
+    }
 }
 
-impl Dataset {
-    pub fn new(data: Data, metric: Metric) -> Dataset {
+impl<T: PartialEq> PartialEq for Dataset<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data && self.metric == other.metric
+    }
+}
+
+impl<T> Dataset<T> {
+    pub fn new(data: Data<T>, metric: Metric) -> Dataset<T> {
+        Dataset::with_store(data, metric, Box::new(HashMapStore::default()))
+    }
+
+    /// Like `new`, but with an explicit backing store for the distance
+    /// cache, e.g. `EvictingStore::new(capacity)` for datasets too large to
+    /// cache in full.
+    pub fn with_store(data: Data<T>, metric: Metric, store: Box<dyn DistanceStore>) -> Dataset<T> {
         Dataset {
             data: Box::new(data),
             metric,
-            history: HashMap::new(),
+            cache: Mutex::new(store),
         }
     }
 
     pub fn len(&self) -> Index {
         self.data.len() as Index
     }
-    // pub fn distance(&self, left: Indices, right: Indices) -> Radius {
-    //     left.iter().zip(&right).fold(0, |sum, (a, b)| sum + a + b) as f64
-    // }
 
     fn key(&self, i: Index, j: Index) -> Index {
-        if i == j { 0 }
-        else if i < j { (j * (j - 1) / 2 + i + 1) }
-        else { (i * (i - 1) / 2 + j + 1) }
+        if i == j {
+            0
+        } else if i < j {
+            j * (j - 1) / 2 + i + 1
+        } else {
+            i * (i - 1) / 2 + j + 1
+        }
     }
 
     fn ij(&self, k: Index) -> (Index, Index) {
@@ -35,20 +128,88 @@ impl Dataset {
         let j: Index = k - 1 - i * (i - 1) / 2;
         (i, j)
     }
+}
 
-    fn insert(&self, left: Indices, right: Indices) -> () {
-        let mut keys: HashSet<Index> = HashSet::new();
+impl<T: Copy + Into<f64> + Sync> Dataset<T> {
+    /// Distance between points `i` and `j`: returns the cached value if one
+    /// has been computed before (keyed by the condensed-matrix index
+    /// `key(i, j)`), otherwise computes it via `metric`, caches it, and
+    /// returns it.
+    pub fn distance(&self, i: Index, j: Index) -> Radius {
+        let key = self.key(i, j);
+        if let Some(cached) = self.cache.lock().unwrap().get(key) {
+            return cached;
+        }
+        let distance = self.compute(i, j);
+        self.cache.lock().unwrap().put(key, distance);
+        distance
+    }
+
+    /// Computes the dense `left.len() x right.len()` block of distances
+    /// between `left` and `right`. Only the keys not already cached are
+    /// computed, and those are computed in parallel.
+    pub fn distance_matrix(&self, left: &Indices, right: &Indices) -> Vec<Vec<Radius>> {
+        let mut missing: HashSet<Index> = HashSet::new();
         for &i in left.iter() {
             for &j in right.iter() {
-                keys.insert(self.key(i, j));
+                let key = self.key(i, j);
+                if self.cache.lock().unwrap().get(key).is_none() {
+                    missing.insert(key);
+                }
             }
         }
 
-        let mut new_keys: Indices = vec![];
-        for k in keys.iter() {
-            if !self.history.contains_key(k) {
-                new_keys.push(*k);
-            }
+        let computed: Vec<(Index, Radius)> = missing
+            .into_par_iter()
+            .map(|key| {
+                // `key == 0` means `i == j` (see `key`'s base case), and
+                // every point is at distance `0` from itself, so there's no
+                // `(i, j)` pair to recover via `ij` (which isn't defined at
+                // `0`) or to feed through the metric.
+                let distance = if key == 0 {
+                    0.
+                } else {
+                    let (i, j) = self.ij(key);
+                    self.compute(i, j)
+                };
+                (key, distance)
+            })
+            .collect();
+
+        let mut cache = self.cache.lock().unwrap();
+        for (key, distance) in computed {
+            cache.put(key, distance);
+        }
+        drop(cache);
+
+        left.iter()
+            .map(|&i| {
+                right
+                    .iter()
+                    .map(|&j| self.distance(i, j))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn compute(&self, i: Index, j: Index) -> Radius {
+        let a = self.data.row(i);
+        let b = self.data.row(j);
+        match self.metric.as_str() {
+            "euclidean" => a
+                .iter()
+                .zip(b.iter())
+                .map(|(&x, &y)| {
+                    let diff = x.into() - y.into();
+                    diff * diff
+                })
+                .sum::<f64>()
+                .sqrt(),
+            _ => a
+                .iter()
+                .zip(b.iter())
+                .map(|(&x, &y)| (x.into() - y.into()).abs())
+                .sum(),
         }
     }
 }
@@ -59,6 +220,45 @@ mod tests {
 
     #[test]
     fn new() {
-        let dataset = Dataset::new(vec![0, 0], String::from("euclidean"));
+        let dataset: Dataset<i32> = Dataset::new(ndarray::arr2(&[[0], [0]]), String::from("euclidean"));
+        assert_eq!(dataset.len(), 2);
+    }
+
+    #[test]
+    fn distance_is_cached() {
+        let dataset = Dataset::new(ndarray::arr2(&[[0., 0.], [3., 4.]]), String::from("euclidean"));
+        assert_eq!(dataset.distance(0, 1), 5.);
+        // Second call must hit the cache and return the same value.
+        assert_eq!(dataset.distance(0, 1), 5.);
+    }
+
+    #[test]
+    fn distance_matrix_matches_pairwise() {
+        let dataset = Dataset::new(
+            ndarray::arr2(&[[0., 0.], [3., 4.], [6., 8.]]),
+            String::from("euclidean"),
+        );
+        let left: Indices = vec![0, 1].into_iter().collect();
+        let right: Indices = vec![2].into_iter().collect();
+        let block = dataset.distance_matrix(&left, &right);
+        assert_eq!(block[0][0], dataset.distance(0, 2));
+        assert_eq!(block[1][0], dataset.distance(1, 2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn distance_matrix_handles_shared_indices() {
+        let dataset = Dataset::new(
+            ndarray::arr2(&[[0., 0.], [3., 4.], [6., 8.]]),
+            String::from("euclidean"),
+        );
+        let indices: Indices = vec![0, 1, 2].into_iter().collect();
+        // left and right overlap, so some requested pairs have i == j and
+        // hit the key(i, i) == 0 case.
+        let block = dataset.distance_matrix(&indices, &indices);
+        for (row, &i) in indices.iter().enumerate() {
+            for (col, &j) in indices.iter().enumerate() {
+                assert_eq!(block[row][col], dataset.distance(i, j));
+            }
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use indexmap::IndexSet;
+use ndarray::Array2;
+
+/// Row index into a `Dataset`, and the unit used for condensed-matrix keys.
+pub type Index = usize;
+
+/// A pairwise distance, as returned by a metric.
+pub type Radius = f64;
+
+/// Name of a metric (e.g. `"euclidean"`), looked up by `Dataset`.
+pub type Metric = String;
+
+/// Row-major point data backing a `Dataset`.
+pub type Data<T> = Array2<T>;
+
+/// An insertion-ordered set of point indices: dedups automatically, offers
+/// O(1) `contains`, and (unlike a plain `HashSet`) keeps a stable iteration
+/// order and supports by-position access. This is what lets pole-assignment
+/// and component merging accumulate indices without worrying about
+/// duplicates, and keeps `Cluster` equality well-defined regardless of the
+/// order indices were collected in.
+pub type Indices = IndexSet<Index>;